@@ -1,16 +1,22 @@
+#[cfg(feature = "openqasm")]
+mod qasm;
 mod results;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 use std::f64::consts::PI;
 use std::{collections::HashMap, env::args, fs::File, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use fasteval::Evaler;
 #[cfg(feature = "mpi")]
 use mpi::topology::Communicator;
 use qulacs_bridge::ffi::{
     add_gate_copy, merge, new_cnot_gate, new_h_gate, new_pauli_rotation_gate, new_quantum_circuit,
     new_quantum_state, new_r_x_gate, new_r_z_gate, new_x_gate, new_y_gate, new_z_gate,
-    set_zero_state, update_quantum_state, Pauli, QuantumCircuit,
+    pauli_expectation_value, set_zero_state, update_quantum_state, Pauli, QuantumCircuit,
 };
 #[cfg(not(feature = "mpi"))]
 use qulacs_bridge::ffi::{get_classical_register, new_measurement};
@@ -23,9 +29,10 @@ use serde::{Deserialize, Serialize};
 use tket_json_rs::{circuit_json::Command, OpType, SerialCircuit};
 
 #[cfg(feature = "mpi")]
-use crate::results::{convert_samples, BackendResult};
+use crate::results::{aggregate_samples, convert_samples, BackendResult};
 #[cfg(not(feature = "mpi"))]
-use crate::results::{convert_shots, BackendResult};
+use crate::results::{aggregate_shots, convert_shots, BackendResult};
+use crate::results::OutcomeArray;
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 struct NodeDefinition {
@@ -34,6 +41,9 @@ struct NodeDefinition {
     outputs: HashMap<String, PathBuf>,
     done_path: PathBuf,
     log_path: Option<PathBuf>,
+    /// Fold shots into an outcome histogram instead of recording every shot.
+    #[serde(default)]
+    aggregate: bool,
 }
 
 fn get_arg(cmd: &Command, index: usize) -> u32 {
@@ -120,6 +130,20 @@ fn convert_circuit(
 
                 new_pauli_rotation_gate(&[index_1, index_2], &[Pauli::Z, Pauli::Z], alpha)
             }
+            // Qulacs rotations are the opposite direction to pytket
+            // rotations, as above.
+            OpType::Rx => {
+                let index = get_arg(&command, 0);
+                let alpha = -evaluator.eval_param(&command, 0).unwrap() * PI;
+
+                new_r_x_gate(index, alpha)
+            }
+            OpType::Rz => {
+                let index = get_arg(&command, 0);
+                let alpha = -evaluator.eval_param(&command, 0).unwrap() * PI;
+
+                new_r_z_gate(index, alpha)
+            }
             // Mid-circuit measurement is not supported for MPI.
             #[cfg(feature = "mpi")]
             OpType::Measure => {
@@ -148,6 +172,7 @@ fn simulate_circuit(
     circuit: &SerialCircuit,
     n_shot: u32,
     mut rng: &mut Box<dyn RngCore>,
+    aggregate: bool,
 ) -> Result<BackendResult> {
     let n_qubits = circuit.qubits.len();
     let bits = circuit.bits.clone();
@@ -158,15 +183,25 @@ fn simulate_circuit(
 
     // TODO: We need the same seed on each node for MPI?
     #[cfg(feature = "mpi")]
-    let shots = {
+    let (shots, counts) = {
         set_zero_state(&state);
         update_quantum_state(&circuit, &state, rng.random());
         let samples = quantum_state_sampling(&state, n_shot, rng.random());
-        convert_samples(n_shot as usize, samples)
+        if aggregate {
+            (
+                OutcomeArray {
+                    width: n_shot as usize,
+                    array: Vec::new(),
+                },
+                aggregate_samples(n_shot as usize, samples),
+            )
+        } else {
+            (convert_samples(n_shot as usize, samples), Vec::new())
+        }
     };
 
     #[cfg(not(feature = "mpi"))]
-    let shots = {
+    let (shots, counts) = {
         let mut shots = Vec::new();
         for _ in 0..n_shot {
             set_zero_state(&state);
@@ -174,11 +209,23 @@ fn simulate_circuit(
             let register = get_classical_register(&state);
             shots.push(register);
         }
-        convert_shots(shots)
+        if aggregate {
+            let width = shots.first().map(Vec::len).unwrap_or(0);
+            (
+                OutcomeArray {
+                    width,
+                    array: Vec::new(),
+                },
+                aggregate_shots(shots),
+            )
+        } else {
+            (convert_shots(shots), Vec::new())
+        }
     };
     Ok(BackendResult {
         bits,
         qubits,
+        counts,
         shots,
     })
 }
@@ -187,11 +234,73 @@ fn simulate_circuits(
     list_circ: &[SerialCircuit],
     n_shot: u32,
     seed: Option<u64>,
+    aggregate: bool,
 ) -> Result<Vec<BackendResult>> {
     let mut rng = new_rng(seed);
     list_circ
         .iter()
-        .map(|circuit| simulate_circuit(circuit, n_shot, &mut rng))
+        .map(|circuit| simulate_circuit(circuit, n_shot, &mut rng, aggregate))
+        .collect()
+}
+
+/// Parses a Pauli string (one `I`/`X`/`Y`/`Z` symbol per qubit register,
+/// in register order) into the sparse `(indices, paulis)` form qulacs
+/// expects, dropping identity terms.
+fn parse_pauli_string(pauli_string: &str) -> Result<(Vec<u32>, Vec<Pauli>)> {
+    let mut indices = Vec::new();
+    let mut paulis = Vec::new();
+
+    for (index, symbol) in pauli_string.chars().enumerate() {
+        let pauli = match symbol {
+            'I' => continue,
+            'X' => Pauli::X,
+            'Y' => Pauli::Y,
+            'Z' => Pauli::Z,
+            other => bail!("unsupported Pauli symbol `{other}` in observable `{pauli_string}`"),
+        };
+        indices.push(index.try_into().unwrap());
+        paulis.push(pauli);
+    }
+
+    Ok((indices, paulis))
+}
+
+/// Prepares the statevector for `circuit` once and evaluates the exact
+/// expectation value of each Pauli string against it, avoiding the
+/// per-shot `set_zero_state`/`update_quantum_state` loop `simulate_circuit`
+/// uses for sampling.
+fn evaluate_expectations(circuit: &SerialCircuit, observables: &[String]) -> Result<Vec<f64>> {
+    if circuit
+        .commands
+        .iter()
+        .any(|command| command.op.op_type == OpType::Measure)
+    {
+        bail!("expectation evaluation requires a measurement-free circuit: mid-circuit measurement would non-deterministically collapse the state before the Pauli expectation is evaluated");
+    }
+
+    let n_qubits = circuit.qubits.len();
+
+    if let Some(pauli_string) = observables.iter().find(|o| o.chars().count() != n_qubits) {
+        bail!(
+            "observable `{pauli_string}` has {} symbols but circuit has {n_qubits} qubits",
+            pauli_string.chars().count()
+        );
+    }
+
+    let mut rng = new_rng(None);
+
+    let state = new_quantum_state(n_qubits.try_into().unwrap(), true);
+    let qulacs_circuit = convert_circuit(circuit, &mut rng)?;
+
+    set_zero_state(&state);
+    update_quantum_state(&qulacs_circuit, &state, rng.random());
+
+    observables
+        .iter()
+        .map(|pauli_string| {
+            let (indices, paulis) = parse_pauli_string(pauli_string)?;
+            Ok(pauli_expectation_value(&state, &indices, &paulis))
+        })
         .collect()
 }
 
@@ -204,11 +313,19 @@ fn run(node_definition: &NodeDefinition) -> Result<()> {
             let n_shots_file = File::open(&node_definition.inputs["n_shots"])?;
             let n_shots: u32 = serde_json::from_reader(&n_shots_file)?;
 
-            let results = simulate_circuits(&circuits, n_shots, None)?;
+            let results = simulate_circuits(&circuits, n_shots, None, node_definition.aggregate)?;
 
             let outputs_file = File::create(&node_definition.outputs["backend_results"])?;
             serde_json::to_writer(outputs_file, &results)?;
 
+            #[cfg(feature = "binary")]
+            if let Some(path) = node_definition.outputs.get("backend_results_bin") {
+                let mut bin_file = File::create(path)?;
+                for result in &results {
+                    result.write(&mut bin_file)?;
+                }
+            }
+
             File::create(&node_definition.done_path)?;
             Ok(())
         }
@@ -220,15 +337,54 @@ fn run(node_definition: &NodeDefinition) -> Result<()> {
             let n_shots: u32 = serde_json::from_reader(&n_shots_file)?;
 
             let mut rng = new_rng(None);
-            let result = simulate_circuit(&circuit, n_shots, &mut rng)?;
+            let result = simulate_circuit(&circuit, n_shots, &mut rng, node_definition.aggregate)?;
 
             let output_file = File::create(&node_definition.outputs["backend_result"])?;
             serde_json::to_writer(output_file, &result)?;
 
+            #[cfg(feature = "binary")]
+            if let Some(path) = node_definition.outputs.get("backend_results_bin") {
+                let mut bin_file = File::create(path)?;
+                result.write(&mut bin_file)?;
+            }
+
             File::create(&node_definition.done_path)?;
             Ok(())
         }
 
+        #[cfg(feature = "openqasm")]
+        "submit_single_qasm" => {
+            let source = std::fs::read_to_string(&node_definition.inputs["circuit"])?;
+            let circuit = qasm::parse(&source)?;
+
+            let n_shots_file = File::open(&node_definition.inputs["n_shots"])?;
+            let n_shots: u32 = serde_json::from_reader(&n_shots_file)?;
+
+            let mut rng = new_rng(None);
+            let result = simulate_circuit(&circuit, n_shots, &mut rng, node_definition.aggregate)?;
+
+            let output_file = File::create(&node_definition.outputs["backend_result"])?;
+            serde_json::to_writer(output_file, &result)?;
+
+            File::create(&node_definition.done_path)?;
+            Ok(())
+        }
+
+        "expectation" => {
+            let circuit_file = File::open(&node_definition.inputs["circuit"])?;
+            let circuit: SerialCircuit = serde_json::from_reader(&circuit_file)?;
+
+            let observables_file = File::open(&node_definition.inputs["observables"])?;
+            let observables: Vec<String> = serde_json::from_reader(&observables_file)?;
+
+            let expectations = evaluate_expectations(&circuit, &observables)?;
+
+            let output_file = File::create(&node_definition.outputs["expectations"])?;
+            serde_json::to_writer(output_file, &expectations)?;
+
+            File::create(&node_definition.done_path)?;
+            Ok(())
+        }
 
         #[cfg(feature = "mpi")]
         "submit_single_mpi" => {
@@ -244,7 +400,7 @@ fn run(node_definition: &NodeDefinition) -> Result<()> {
             let n_shots: u32 = serde_json::from_reader(&n_shots_file)?;
 
             let mut rng = new_rng(None);
-            let result = simulate_circuit(&circuit, n_shots, &mut rng)?;
+            let result = simulate_circuit(&circuit, n_shots, &mut rng, node_definition.aggregate)?;
 
             if rank == 0 {
                 let output_file = File::create(&node_definition.outputs["backend_result"])?;
@@ -267,6 +423,11 @@ fn main() -> Result<()> {
         .next()
         .expect("expected a node definition path as first argument.");
 
+    #[cfg(feature = "server")]
+    if node_definition_path == "serve" {
+        return server::run_stdin_server();
+    }
+
     let node_definition_file = File::open(node_definition_path)?;
     let node_definition: NodeDefinition = serde_json::from_reader(node_definition_file)?;
 
@@ -278,7 +439,7 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
-    use tket_json_rs::circuit_json::Operation;
+    use tket_json_rs::{circuit_json::Operation, register::Register};
 
     use super::*;
 
@@ -334,7 +495,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(None);
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -352,7 +513,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(Some(1));
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -372,7 +533,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(None);
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -390,7 +551,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(Some(1));
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -410,7 +571,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(None);
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -422,13 +583,163 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn aggregate_mode_folds_shots_into_counts() -> Result<()> {
+        let circuit = test_circuit(
+            2,
+            2,
+            vec![
+                Command {
+                    op: Operation::from_optype(OpType::H),
+                    args: vec![qubit(0)],
+                    opgroup: None,
+                },
+                Command {
+                    op: Operation::from_optype(OpType::CX),
+                    args: vec![qubit(0), qubit(1)],
+                    opgroup: None,
+                },
+                Command {
+                    op: Operation::from_optype(OpType::Measure),
+                    args: vec![qubit(0), bit(0)],
+                    opgroup: None,
+                },
+                Command {
+                    op: Operation::from_optype(OpType::Measure),
+                    args: vec![qubit(1), bit(1)],
+                    opgroup: None,
+                },
+            ],
+        );
+
+        let mut rng = new_rng(None);
+        let result = simulate_circuit(&circuit, 10, &mut rng, true)?;
+
+        assert!(result.shots.array.is_empty());
+        assert!(!result.counts.is_empty());
+        assert_eq!(result.counts.iter().map(|c| c.count).sum::<i32>(), 10);
+        assert!(result
+            .counts
+            .iter()
+            .all(|c| c.outcome.array[0][0] == 0 || c.outcome.array[0][0] == 192));
+
+        Ok(())
+    }
+
+    fn qubit(index: u32) -> Register {
+        Register("q".to_string(), vec![index])
+    }
+
+    fn bit(index: u32) -> Register {
+        Register("c".to_string(), vec![index])
+    }
+
+    /// Builds a [`SerialCircuit`] by hand, reusing tket's own JSON schema
+    /// like `qasm::build_circuit` does, instead of depending on `data/*.json`
+    /// fixture files for circuits that don't need one.
+    fn test_circuit(n_qubits: u32, n_bits: u32, commands: Vec<Command>) -> SerialCircuit {
+        let qubits: Vec<Register> = (0..n_qubits).map(qubit).collect();
+        let bits: Vec<Register> = (0..n_bits).map(bit).collect();
+        let implicit_permutation: Vec<(&Register, &Register)> =
+            qubits.iter().map(|q| (q, q)).collect();
+
+        let value = serde_json::json!({
+            "qubits": qubits,
+            "bits": bits,
+            "commands": commands,
+            "implicit_permutation": implicit_permutation,
+            "created_qubits": Vec::<Register>::new(),
+            "discarded_qubits": Vec::<Register>::new(),
+            "phase": "0",
+            "name": serde_json::Value::Null,
+        });
+
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn pauli_eq(a: &Pauli, b: &Pauli) -> bool {
+        matches!(
+            (a, b),
+            (Pauli::X, Pauli::X) | (Pauli::Y, Pauli::Y) | (Pauli::Z, Pauli::Z)
+        )
+    }
+
+    #[rstest]
+    #[case("III", vec![], vec![])]
+    #[case("X", vec![0], vec![Pauli::X])]
+    #[case("IZ", vec![1], vec![Pauli::Z])]
+    #[case("XYZ", vec![0, 1, 2], vec![Pauli::X, Pauli::Y, Pauli::Z])]
+    fn parse_pauli_string_examples(
+        #[case] input: &str,
+        #[case] expected_indices: Vec<u32>,
+        #[case] expected_paulis: Vec<Pauli>,
+    ) {
+        let (indices, paulis) = parse_pauli_string(input).unwrap();
+        assert_eq!(indices, expected_indices);
+        assert_eq!(paulis.len(), expected_paulis.len());
+        assert!(paulis
+            .iter()
+            .zip(expected_paulis.iter())
+            .all(|(p, e)| pauli_eq(p, e)));
+    }
+
+    #[test]
+    fn parse_pauli_string_rejects_unsupported_symbol() {
+        assert!(parse_pauli_string("IXA").is_err());
+    }
+
+    #[test]
+    fn expectation_value_of_bell_pair() -> Result<()> {
+        let circuit = test_circuit(
+            2,
+            0,
+            vec![
+                Command {
+                    op: Operation::from_optype(OpType::H),
+                    args: vec![qubit(0)],
+                    opgroup: None,
+                },
+                Command {
+                    op: Operation::from_optype(OpType::CX),
+                    args: vec![qubit(0), qubit(1)],
+                    opgroup: None,
+                },
+            ],
+        );
+
+        let expectations =
+            evaluate_expectations(&circuit, &["ZZ".to_string(), "XX".to_string()])?;
+
+        assert!((expectations[0] - 1.0).abs() < 1e-9);
+        assert!((expectations[1] - 1.0).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn expectation_value_of_zero_state() -> Result<()> {
+        let circuit = test_circuit(1, 0, Vec::new());
+
+        let expectations = evaluate_expectations(&circuit, &["Z".to_string()])?;
+
+        assert!((expectations[0] - 1.0).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn expectation_rejects_observable_length_mismatch() {
+        let circuit = test_circuit(1, 0, Vec::new());
+        assert!(evaluate_expectations(&circuit, &["ZZ".to_string()]).is_err());
+    }
+
     #[test]
     fn zz_phase_circuit_seeded() -> Result<()> {
         let file = File::open("data/zzphase.json")?;
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(Some(1));
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -448,7 +759,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(None);
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -466,7 +777,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(Some(1));
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -486,7 +797,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(None);
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
 
@@ -499,7 +810,7 @@ mod tests {
         let circuit: SerialCircuit = serde_json::from_reader(&file)?;
 
         let mut rng = new_rng(Some(1));
-        let result = simulate_circuit(&circuit, 10, &mut rng)?;
+        let result = simulate_circuit(&circuit, 10, &mut rng, false)?;
 
         assert_eq!(result.shots.array.len(), 10);
         insta::assert_debug_snapshot!(result.shots);
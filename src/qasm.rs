@@ -0,0 +1,267 @@
+//! A hand-written parser for the OpenQASM 2.0 subset `convert_circuit`
+//! understands, producing the same [`SerialCircuit`] representation `run()`
+//! reads from tket JSON.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use tket_json_rs::{
+    circuit_json::{Command, Operation},
+    register::Register,
+    OpType, SerialCircuit,
+};
+
+/// Parses OpenQASM 2.0 source into a [`SerialCircuit`].
+///
+/// `convert_circuit` addresses qubits/bits as a single flat index space, so
+/// each `qreg`/`creg` is assigned a distinct offset into that space in
+/// declaration order rather than starting back at index 0.
+pub fn parse(source: &str) -> Result<SerialCircuit> {
+    let mut qregs: Vec<(String, u32)> = Vec::new();
+    let mut cregs: Vec<(String, u32)> = Vec::new();
+    let mut qubit_offsets: HashMap<String, u32> = HashMap::new();
+    let mut bit_offsets: HashMap<String, u32> = HashMap::new();
+    let mut commands = Vec::new();
+
+    for statement in statements(source) {
+        let mut words = statement.splitn(2, ' ');
+        let keyword = words.next().unwrap_or_default();
+        let rest = words.next().unwrap_or_default().trim();
+
+        match keyword {
+            "OPENQASM" | "include" => {}
+            "qreg" => {
+                let (name, size) = parse_register_decl(rest)?;
+                qubit_offsets.insert(name.clone(), qregs.iter().map(|(_, size)| size).sum());
+                qregs.push((name, size));
+            }
+            "creg" => {
+                let (name, size) = parse_register_decl(rest)?;
+                bit_offsets.insert(name.clone(), cregs.iter().map(|(_, size)| size).sum());
+                cregs.push((name, size));
+            }
+            "measure" => commands.push(parse_measure(rest, &qubit_offsets, &bit_offsets)?),
+            _ => commands.push(parse_gate(keyword, rest, &qubit_offsets)?),
+        }
+    }
+
+    build_circuit(expand_registers(&qregs), expand_registers(&cregs), commands)
+}
+
+/// Splits QASM source into whitespace-normalized, comment-stripped
+/// statements.
+fn statements(source: &str) -> Vec<String> {
+    let without_comments = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    without_comments
+        .split(';')
+        .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parses a `name[size]` declaration, e.g. `q[2]` in `qreg q[2]`.
+fn parse_register_decl(decl: &str) -> Result<(String, u32)> {
+    let (name, size) = decl
+        .trim_end_matches(']')
+        .split_once('[')
+        .with_context(|| format!("malformed register declaration `{decl}`"))?;
+    Ok((name.to_string(), size.parse()?))
+}
+
+/// Parses a `name[index]` reference, e.g. the `q[0]` in `x q[0];`.
+fn parse_register_ref(reference: &str) -> Result<(String, u32)> {
+    parse_register_decl(reference)
+}
+
+/// Resolves a register-local index to its position in the flat index space.
+fn global_index(offsets: &HashMap<String, u32>, name: &str, local_index: u32) -> Result<u32> {
+    let offset = offsets
+        .get(name)
+        .with_context(|| format!("reference to undeclared register `{name}`"))?;
+    Ok(offset + local_index)
+}
+
+fn parse_measure(
+    rest: &str,
+    qubit_offsets: &HashMap<String, u32>,
+    bit_offsets: &HashMap<String, u32>,
+) -> Result<Command> {
+    let (qubit_ref, bit_ref) = rest
+        .split_once("->")
+        .with_context(|| format!("malformed measure statement `measure {rest}`"))?;
+    let (qreg, qidx) = parse_register_ref(qubit_ref.trim())?;
+    let (creg, cidx) = parse_register_ref(bit_ref.trim())?;
+    let qubit_index = global_index(qubit_offsets, &qreg, qidx)?;
+    let bit_index = global_index(bit_offsets, &creg, cidx)?;
+
+    Ok(Command {
+        op: Operation::from_optype(OpType::Measure),
+        args: vec![Register(qreg, vec![qubit_index]), Register(creg, vec![bit_index])],
+        opgroup: None,
+    })
+}
+
+fn parse_gate(keyword: &str, args_text: &str, qubit_offsets: &HashMap<String, u32>) -> Result<Command> {
+    let (name, params) = match keyword.split_once('(') {
+        Some((name, params)) => {
+            let params = params
+                .trim_end_matches(')')
+                .split(',')
+                .map(|p| angle_param(p.trim()))
+                .collect::<Vec<_>>();
+            (name, params)
+        }
+        None => (keyword, Vec::new()),
+    };
+
+    let args = args_text
+        .split(',')
+        .map(|arg| {
+            let (name, local_index) = parse_register_ref(arg.trim())?;
+            let index = global_index(qubit_offsets, &name, local_index)?;
+            Ok(Register(name, vec![index]))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let op_type = match name {
+        "x" => OpType::X,
+        "y" => OpType::Y,
+        "z" => OpType::Z,
+        "h" => OpType::H,
+        "cx" => OpType::CX,
+        "rx" => OpType::Rx,
+        "rz" => OpType::Rz,
+        "rzz" => OpType::ZZPhase,
+        other => bail!("unsupported QASM gate `{other}`"),
+    };
+
+    let mut op = Operation::from_optype(op_type);
+    if !params.is_empty() {
+        op.params = Some(params);
+    }
+
+    Ok(Command {
+        op,
+        args,
+        opgroup: None,
+    })
+}
+
+/// Rewrites a raw QASM angle expression (in radians) into tket's
+/// convention of a multiple of `pi`, which `Evaluator` expects.
+fn angle_param(expr: &str) -> String {
+    format!("({expr})/pi")
+}
+
+/// Expands `(name, size)` declarations, in order, into one [`Register`]
+/// per individual qubit/bit.
+fn expand_registers(registers: &[(String, u32)]) -> Vec<Register> {
+    registers
+        .iter()
+        .flat_map(|(name, size)| (0..*size).map(move |index| Register(name.clone(), vec![index])))
+        .collect()
+}
+
+/// Assembles a [`SerialCircuit`] from parsed registers and commands via
+/// tket's own JSON schema, rather than hand-rolling every struct field.
+fn build_circuit(
+    qubits: Vec<Register>,
+    bits: Vec<Register>,
+    commands: Vec<Command>,
+) -> Result<SerialCircuit> {
+    let implicit_permutation: Vec<(&Register, &Register)> =
+        qubits.iter().map(|q| (q, q)).collect();
+
+    let value = serde_json::json!({
+        "qubits": qubits,
+        "bits": bits,
+        "commands": commands,
+        "implicit_permutation": implicit_permutation,
+        "created_qubits": Vec::<Register>::new(),
+        "discarded_qubits": Vec::<Register>::new(),
+        "phase": "0",
+        "name": serde_json::Value::Null,
+    });
+
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use tket_json_rs::circuit_json::Operation;
+
+    use super::*;
+    use crate::Evaluator;
+
+    #[test]
+    fn parses_bell_pair_program() {
+        let source = "
+            OPENQASM 2.0;
+            include \"qelib1.inc\";
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            cx q[0],q[1];
+            measure q[0] -> c[0];
+            measure q[1] -> c[1];
+        ";
+
+        let circuit = parse(source).unwrap();
+
+        assert_eq!(circuit.qubits.len(), 2);
+        assert_eq!(circuit.bits.len(), 2);
+        assert_eq!(circuit.commands.len(), 4);
+        assert_eq!(circuit.commands[0].op.op_type, OpType::H);
+        assert_eq!(circuit.commands[1].op.op_type, OpType::CX);
+        assert_eq!(circuit.commands[2].op.op_type, OpType::Measure);
+    }
+
+    #[test]
+    fn rebases_indices_across_multiple_qregs() {
+        let source = "
+            qreg q[2];
+            qreg anc[1];
+            x q[0];
+            x anc[0];
+        ";
+
+        let circuit = parse(source).unwrap();
+        assert_eq!(circuit.qubits.len(), 3);
+
+        let q0_index = circuit.commands[0].args[0].1[0];
+        let anc0_index = circuit.commands[1].args[0].1[0];
+        assert_ne!(q0_index, anc0_index);
+    }
+
+    #[test]
+    fn rejects_unsupported_gate() {
+        assert!(parse("qreg q[1];\nt q[0];").is_err());
+    }
+
+    #[rstest]
+    #[case("pi/2", std::f64::consts::FRAC_PI_2)]
+    #[case("pi", std::f64::consts::PI)]
+    #[case("2*pi", 2.0 * std::f64::consts::PI)]
+    fn angle_param_evaluates_back_to_radians(
+        #[case] qasm_angle: &str,
+        #[case] expected_radians: f64,
+    ) {
+        let mut op = Operation::from_optype(OpType::Rz);
+        op.params = Some(vec![angle_param(qasm_angle)]);
+        let command = Command {
+            op,
+            args: Vec::new(),
+            opgroup: None,
+        };
+
+        let mut evaluator = Evaluator::new();
+        let turns = evaluator.eval_param(&command, 0).unwrap();
+        assert!((turns * std::f64::consts::PI - expected_radians).abs() < 1e-10);
+    }
+}
@@ -2,29 +2,177 @@ use bitvec::{
     order::{Lsb0, Msb0},
     vec::BitVec,
 };
+#[cfg(feature = "binary")]
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Serialize};
-use tket_json_rs::register::{Bit, Qubit};
+use std::collections::HashMap;
+#[cfg(feature = "binary")]
+use std::io::{self, Read, Write};
+use tket_json_rs::register::{Bit, Qubit, Register};
 
 #[derive(Deserialize, Serialize, Hash, Eq, PartialEq, Debug)]
 pub struct BackendResult {
     pub qubits: Vec<Qubit>,
     pub bits: Vec<Bit>,
-    // pub counts: Vec<Count>,
+    pub counts: Vec<Count>,
     pub shots: OutcomeArray,
 }
 
+#[cfg(feature = "binary")]
+fn write_register<W: Write>(mut w: W, register: &Register) -> io::Result<()> {
+    let name = register.0.as_bytes();
+    w.write_u64::<BigEndian>(name.len() as u64)?;
+    w.write_all(name)?;
+    w.write_u64::<BigEndian>(register.1.len() as u64)?;
+    for index in &register.1 {
+        w.write_u32::<BigEndian>(*index)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "binary")]
+fn read_register<R: Read>(mut r: R) -> io::Result<Register> {
+    let name_len = r.read_u64::<BigEndian>()? as usize;
+    let mut name = vec![0u8; name_len];
+    r.read_exact(&mut name)?;
+    let name = String::from_utf8(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let index_len = r.read_u64::<BigEndian>()?;
+    let mut index = Vec::with_capacity(index_len as usize);
+    for _ in 0..index_len {
+        index.push(r.read_u32::<BigEndian>()?);
+    }
+
+    Ok(Register(name, index))
+}
+
+#[cfg(feature = "binary")]
+fn write_registers<W: Write>(mut w: W, registers: &[Register]) -> io::Result<()> {
+    w.write_u64::<BigEndian>(registers.len() as u64)?;
+    for register in registers {
+        write_register(&mut w, register)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "binary")]
+fn read_registers<R: Read>(mut r: R) -> io::Result<Vec<Register>> {
+    let count = r.read_u64::<BigEndian>()?;
+    (0..count).map(|_| read_register(&mut r)).collect()
+}
+
+#[cfg(feature = "binary")]
+impl BackendResult {
+    /// Writes a self-describing binary encoding of this result: the qubit
+    /// and bit registers, the outcome histogram (if any), then the packed
+    /// shot table.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write_registers(&mut w, &self.qubits)?;
+        write_registers(&mut w, &self.bits)?;
+
+        w.write_u64::<BigEndian>(self.counts.len() as u64)?;
+        for count in &self.counts {
+            count.write(&mut w)?;
+        }
+
+        self.shots.write(&mut w)
+    }
+
+    /// Reads back a [`BackendResult`] written by [`BackendResult::write`].
+    pub fn read<R: Read>(mut r: R) -> io::Result<Self> {
+        let qubits = read_registers(&mut r)?;
+        let bits = read_registers(&mut r)?;
+
+        let count_len = r.read_u64::<BigEndian>()?;
+        let counts = (0..count_len)
+            .map(|_| Count::read(&mut r))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let shots = OutcomeArray::read(&mut r)?;
+
+        Ok(Self {
+            qubits,
+            bits,
+            counts,
+            shots,
+        })
+    }
+}
+
 #[derive(Deserialize, Serialize, Hash, Eq, PartialEq, Debug)]
 pub struct Count {
     pub outcome: OutcomeArray,
     pub count: i32,
 }
 
+#[cfg(feature = "binary")]
+impl Count {
+    /// Writes the packed outcome followed by its `i32` shot count.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        self.outcome.write(&mut w)?;
+        w.write_i32::<BigEndian>(self.count)
+    }
+
+    /// Reads back a [`Count`] written by [`Count::write`].
+    pub fn read<R: Read>(mut r: R) -> io::Result<Self> {
+        let outcome = OutcomeArray::read(&mut r)?;
+        let count = r.read_i32::<BigEndian>()?;
+        Ok(Self { outcome, count })
+    }
+}
+
 #[derive(Deserialize, Serialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
 pub struct OutcomeArray {
     pub width: usize,
     pub array: Vec<Vec<u8>>,
 }
 
+#[cfg(feature = "binary")]
+impl OutcomeArray {
+    /// Writes a self-describing binary encoding: `width` as a big-endian
+    /// `u64`, the row count as a `u64`, then each row's packed bits with no
+    /// per-row length prefix, since every row is exactly
+    /// `ceil(width / 8)` bytes.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_u64::<BigEndian>(self.width as u64)?;
+        w.write_u64::<BigEndian>(self.array.len() as u64)?;
+        for row in &self.array {
+            w.write_all(row)?;
+        }
+        Ok(())
+    }
+
+    /// Reads back an [`OutcomeArray`] written by [`OutcomeArray::write`],
+    /// taking each row as the next `ceil(width / 8)` bytes and erroring with
+    /// [`io::ErrorKind::InvalidData`] if the stream ends partway through a
+    /// row. Rows carry no length prefix, so a row of another length that
+    /// does not run past the end of the stream is not detected.
+    pub fn read<R: Read>(mut r: R) -> io::Result<Self> {
+        let width = r.read_u64::<BigEndian>()? as usize;
+        let row_len = width.div_ceil(8);
+        let rows = r.read_u64::<BigEndian>()?;
+
+        let array = (0..rows)
+            .map(|_| {
+                let mut row = vec![0u8; row_len];
+                r.read_exact(&mut row).map_err(|e| {
+                    if e.kind() == io::ErrorKind::UnexpectedEof {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("row is shorter than the expected {row_len} bytes"),
+                        )
+                    } else {
+                        e
+                    }
+                })?;
+                Ok(row)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self { width, array })
+    }
+}
+
 #[cfg(not(feature = "mpi"))]
 #[inline]
 fn convert_shot(shot: Vec<u64>) -> Vec<u8> {
@@ -45,6 +193,15 @@ pub(crate) fn convert_shots(shots: Vec<Vec<u64>>) -> OutcomeArray {
     }
 }
 
+/// Folds per-shot registers into a histogram of packed outcomes, sorted by
+/// outcome, instead of keeping one row per shot.
+#[cfg(not(feature = "mpi"))]
+#[inline]
+pub(crate) fn aggregate_shots(shots: Vec<Vec<u64>>) -> Vec<Count> {
+    let width = shots.first().map(Vec::len).unwrap_or(0);
+    counts_from_outcomes(width, shots.into_iter().map(convert_shot))
+}
+
 #[cfg(feature = "mpi")]
 #[inline]
 fn convert_sample(trunc: usize, sample: u64) -> Vec<u8> {
@@ -73,6 +230,44 @@ pub(crate) fn convert_samples(width: usize, samples: Vec<u64>) -> OutcomeArray {
     }
 }
 
+/// Folds sampled outcomes into a histogram, sorted by outcome, instead of
+/// keeping one row per sample.
+#[cfg(feature = "mpi")]
+#[inline]
+pub(crate) fn aggregate_samples(width: usize, samples: Vec<u64>) -> Vec<Count> {
+    use num::integer::div_rem;
+
+    let (div, rem) = div_rem(width, 8);
+    let trunc = div + (if rem > 0 { 1 } else { 0 });
+    counts_from_outcomes(
+        width,
+        samples.into_iter().map(|x| convert_sample(trunc, x)),
+    )
+}
+
+/// Shared histogram fold: keys packed outcome bytes to their shot count and
+/// returns the result sorted by outcome.
+#[inline]
+fn counts_from_outcomes(width: usize, outcomes: impl Iterator<Item = Vec<u8>>) -> Vec<Count> {
+    let mut histogram: HashMap<Vec<u8>, i32> = HashMap::new();
+    for outcome in outcomes {
+        *histogram.entry(outcome).or_insert(0) += 1;
+    }
+
+    let mut counts: Vec<Count> = histogram
+        .into_iter()
+        .map(|(outcome, count)| Count {
+            outcome: OutcomeArray {
+                width,
+                array: vec![outcome],
+            },
+            count,
+        })
+        .collect();
+    counts.sort_by(|a, b| a.outcome.cmp(&b.outcome));
+    counts
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -113,4 +308,80 @@ mod tests {
     ) {
         assert_eq!(convert_sample(trunc, sample), expected);
     }
+
+    #[test]
+    fn counts_from_outcomes_folds_duplicates_and_sorts() {
+        let outcomes = vec![vec![2u8], vec![1u8], vec![2u8]];
+        let counts = counts_from_outcomes(1, outcomes.into_iter());
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].outcome.array, vec![vec![1]]);
+        assert_eq!(counts[0].count, 1);
+        assert_eq!(counts[1].outcome.array, vec![vec![2]]);
+        assert_eq!(counts[1].count, 2);
+    }
+
+    #[cfg(not(feature = "mpi"))]
+    #[test]
+    fn aggregate_shots_folds_duplicate_shots() {
+        let shots = vec![
+            vec![1, 0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0, 0],
+            vec![1, 0, 0, 0, 0, 0, 0, 0],
+        ];
+        let counts = aggregate_shots(shots);
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].outcome.array, vec![vec![0]]);
+        assert_eq!(counts[0].count, 1);
+        assert_eq!(counts[1].outcome.array, vec![vec![128]]);
+        assert_eq!(counts[1].count, 2);
+    }
+
+    #[cfg(feature = "mpi")]
+    #[test]
+    fn aggregate_samples_folds_duplicate_samples() {
+        let counts = aggregate_samples(1, vec![1, 0, 1]);
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].outcome.array, vec![vec![0]]);
+        assert_eq!(counts[0].count, 1);
+        assert_eq!(counts[1].outcome.array, vec![vec![128]]);
+        assert_eq!(counts[1].count, 2);
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn backend_result_binary_round_trip() {
+        let result = BackendResult {
+            qubits: vec![Register("q".to_string(), vec![0]), Register("q".to_string(), vec![1])],
+            bits: vec![Register("c".to_string(), vec![0])],
+            counts: vec![
+                Count {
+                    outcome: OutcomeArray {
+                        width: 2,
+                        array: vec![vec![0]],
+                    },
+                    count: 7,
+                },
+                Count {
+                    outcome: OutcomeArray {
+                        width: 2,
+                        array: vec![vec![192]],
+                    },
+                    count: 3,
+                },
+            ],
+            shots: OutcomeArray {
+                width: 2,
+                array: vec![vec![0], vec![192], vec![0]],
+            },
+        };
+
+        let mut buf = Vec::new();
+        result.write(&mut buf).unwrap();
+
+        let read_back = BackendResult::read(&buf[..]).unwrap();
+        assert_eq!(read_back, result);
+    }
 }
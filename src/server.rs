@@ -0,0 +1,295 @@
+//! A long-running worker that accepts jobs over a channel, as an
+//! alternative to the one-shot `NodeDefinition` file model in `run()`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use anyhow::Result;
+use serde::Deserialize;
+use tket_json_rs::SerialCircuit;
+
+use crate::results::BackendResult;
+use crate::{new_rng, simulate_circuit};
+
+/// Number of attempts, the first one included, made at a circuit that fails
+/// to convert or simulate before the job is reported as failed.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Identifies a job submitted to a [`Worker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+/// A circuit in a job failed to convert or simulate after [`MAX_ATTEMPTS`]
+/// attempts.
+#[derive(Debug)]
+pub struct JobError {
+    pub job_id: JobId,
+    pub attempts: u32,
+    pub message: String,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "job {:?} failed after {} attempt(s): {}",
+            self.job_id, self.attempts, self.message
+        )
+    }
+}
+
+impl std::error::Error for JobError {}
+
+type JobOutcome = Result<Vec<BackendResult>, JobError>;
+
+struct Job {
+    id: JobId,
+    circuits: Vec<SerialCircuit>,
+    n_shots: u32,
+    seed: Option<u64>,
+}
+
+/// A background qulacs worker that accepts jobs over a channel.
+pub struct Worker {
+    next_id: AtomicU64,
+    sender: mpsc::Sender<Job>,
+    results: Arc<Mutex<HashMap<JobId, JobOutcome>>>,
+}
+
+impl Worker {
+    /// Spawns the background thread that drains submitted jobs.
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let results = Arc::new(Mutex::new(HashMap::new()));
+
+        let worker_results = Arc::clone(&results);
+        thread::spawn(move || {
+            for job in receiver {
+                let id = job.id;
+                let outcome = run_job(job);
+                worker_results.lock().unwrap().insert(id, outcome);
+            }
+        });
+
+        Self {
+            next_id: AtomicU64::new(0),
+            sender,
+            results,
+        }
+    }
+
+    /// Submits `circuits` and blocks until their results are ready.
+    pub fn submit_and_confirm(
+        &self,
+        circuits: Vec<SerialCircuit>,
+        n_shots: u32,
+        seed: Option<u64>,
+    ) -> JobOutcome {
+        let id = self.submit(circuits, n_shots, seed);
+        loop {
+            if let Some(outcome) = self.poll(id) {
+                return outcome;
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// Enqueues `circuits` for simulation under their own seeded `rng` and
+    /// returns immediately with a [`JobId`] to pass to [`Worker::poll`].
+    pub fn submit(&self, circuits: Vec<SerialCircuit>, n_shots: u32, seed: Option<u64>) -> JobId {
+        let id = JobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.sender
+            .send(Job {
+                id,
+                circuits,
+                n_shots,
+                seed,
+            })
+            .expect("worker thread should outlive its submitters");
+        id
+    }
+
+    /// Returns the job's outcome once it has finished, or `None` if it's
+    /// still running or `id` is unknown.
+    pub fn poll(&self, id: JobId) -> Option<JobOutcome> {
+        self.results.lock().unwrap().remove(&id)
+    }
+}
+
+#[derive(Deserialize)]
+struct SubmitRequest {
+    circuits: Vec<SerialCircuit>,
+    n_shots: u32,
+    seed: Option<u64>,
+}
+
+/// Runs a [`Worker`] against newline-delimited JSON [`SubmitRequest`]s read
+/// from stdin, writing one JSON result line to stdout per request.
+pub fn run_stdin_server() -> Result<()> {
+    let worker = Worker::spawn();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: SubmitRequest = serde_json::from_str(&line)?;
+        let outcome = worker.submit_and_confirm(request.circuits, request.n_shots, request.seed);
+
+        let response = match outcome {
+            Ok(results) => serde_json::to_string(&results)?,
+            Err(err) => serde_json::to_string(&err.to_string())?,
+        };
+        writeln!(stdout, "{response}")?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+fn run_job(job: Job) -> JobOutcome {
+    let mut rng = new_rng(job.seed);
+    let mut outputs = Vec::with_capacity(job.circuits.len());
+
+    for circuit in &job.circuits {
+        let mut last_error = None;
+
+        let result = (1..=MAX_ATTEMPTS).find_map(|attempt| {
+            // simulate_circuit can panic on unsupported gates; catch it so one
+            // bad circuit doesn't take down the worker thread for every future job.
+            match panic::catch_unwind(AssertUnwindSafe(|| {
+                simulate_circuit(circuit, job.n_shots, &mut rng, false)
+            })) {
+                Ok(Ok(backend_result)) => Some(backend_result),
+                Ok(Err(err)) => {
+                    last_error = Some((attempt, err.to_string()));
+                    None
+                }
+                Err(panic) => {
+                    last_error = Some((attempt, panic_message(panic)));
+                    None
+                }
+            }
+        });
+
+        match result {
+            Some(backend_result) => outputs.push(backend_result),
+            None => {
+                let (attempts, message) = last_error.expect("at least one attempt always runs");
+                return Err(JobError {
+                    job_id: job.id,
+                    attempts,
+                    message,
+                });
+            }
+        }
+    }
+
+    Ok(outputs)
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "simulation panicked with a non-string payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tket_json_rs::{
+        circuit_json::{Command, Operation},
+        register::Register,
+        OpType,
+    };
+
+    use super::*;
+
+    fn qubit(index: u32) -> Register {
+        Register("q".to_string(), vec![index])
+    }
+
+    fn test_circuit(n_qubits: u32, commands: Vec<Command>) -> SerialCircuit {
+        let qubits: Vec<Register> = (0..n_qubits).map(qubit).collect();
+        let implicit_permutation: Vec<(&Register, &Register)> =
+            qubits.iter().map(|q| (q, q)).collect();
+
+        let value = serde_json::json!({
+            "qubits": qubits,
+            "bits": Vec::<Register>::new(),
+            "commands": commands,
+            "implicit_permutation": implicit_permutation,
+            "created_qubits": Vec::<Register>::new(),
+            "discarded_qubits": Vec::<Register>::new(),
+            "phase": "0",
+            "name": serde_json::Value::Null,
+        });
+
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn single_qubit_h_circuit() -> SerialCircuit {
+        test_circuit(
+            1,
+            vec![Command {
+                op: Operation::from_optype(OpType::H),
+                args: vec![qubit(0)],
+                opgroup: None,
+            }],
+        )
+    }
+
+    /// A circuit `convert_circuit` has no match arm for, so it panics via
+    /// `unimplemented!()` instead of returning an `Err`.
+    fn unsupported_gate_circuit() -> SerialCircuit {
+        test_circuit(
+            1,
+            vec![Command {
+                op: Operation::from_optype(OpType::Barrier),
+                args: vec![qubit(0)],
+                opgroup: None,
+            }],
+        )
+    }
+
+    #[test]
+    fn submit_and_confirm_round_trip() {
+        let worker = Worker::spawn();
+
+        let results = worker
+            .submit_and_confirm(vec![single_qubit_h_circuit()], 5, Some(1))
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].shots.array.len(), 5);
+    }
+
+    #[test]
+    fn panicking_circuit_fails_the_job_without_killing_the_worker() {
+        let worker = Worker::spawn();
+
+        let err = worker
+            .submit_and_confirm(vec![unsupported_gate_circuit()], 1, None)
+            .unwrap_err();
+        assert_eq!(err.attempts, MAX_ATTEMPTS);
+
+        // If the panic had unwound through the worker thread, the call above
+        // would never have returned, and this one would panic at the
+        // `.expect()` in `Worker::submit`.
+        let results = worker
+            .submit_and_confirm(vec![single_qubit_h_circuit()], 3, Some(1))
+            .unwrap();
+        assert_eq!(results[0].shots.array.len(), 3);
+    }
+}
@@ -0,0 +1,22 @@
+//! Browser entry point for the (non-MPI) simulation path, exposed via
+//! `wasm-bindgen`. Mirrors the `submit`/`submit_single` nodes in `run()`,
+//! but reads a circuit from a `JsValue` instead of a file.
+
+use tket_json_rs::SerialCircuit;
+use wasm_bindgen::prelude::*;
+
+use crate::{new_rng, simulate_circuit};
+
+/// Simulates `circuit_js` (a [`SerialCircuit`] deserialized from JS) for
+/// `n_shots` shots and returns the resulting `BackendResult` as a `JsValue`.
+#[wasm_bindgen]
+pub fn simulate(circuit_js: JsValue, n_shots: u32, seed: Option<u64>) -> Result<JsValue, JsValue> {
+    let circuit: SerialCircuit = serde_wasm_bindgen::from_value(circuit_js)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let mut rng = new_rng(seed);
+    let result = simulate_circuit(&circuit, n_shots, &mut rng, false)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+}